@@ -0,0 +1,158 @@
+use anyhow::Result;
+use atrium_api::app::bsky::embed::images;
+use atrium_api::app::bsky::richtext::facet;
+use atrium_api::types::Union;
+use bsky_sdk::BskyAgent;
+use plotters::prelude::*;
+
+/// Builds the `app.bsky.richtext.facet` entries for a post: one per
+/// `#tag`/`$CASHTAG` occurrence. Facet byte offsets are defined over the
+/// **UTF-8 encoded bytes** of the message, not chars, so offsets are
+/// computed by scanning `message.as_bytes()` rather than `char_indices()`.
+///
+/// The percentage move (e.g. `+2.34%`) is intentionally *not* faceted: a
+/// `#tag` feature requires the tag text to be a valid hashtag, and a string
+/// like `+2.34%` renders as a broken `#+2.34%` link (and may be rejected by
+/// the PDS). There's no link target to point a `Link` feature at either, so
+/// the percentage stays plain text.
+pub fn build_facets(message: &str) -> Vec<facet::MainData> {
+    find_facet_spans(message)
+        .into_iter()
+        .map(|(start, end)| tag_facet(message[tag_start(message, start)..end].to_string(), start, end))
+        .collect()
+}
+
+/// `#`/`$` spans include the marker in their byte range but the tag text
+/// itself starts one byte past it.
+fn tag_start(message: &str, span_start: usize) -> usize {
+    match message.as_bytes().get(span_start) {
+        Some(b'#') | Some(b'$') => span_start + 1,
+        _ => span_start,
+    }
+}
+
+/// Scans `message`'s UTF-8 bytes for `#tag`/`$CASHTAG` runs, returning
+/// their `(byte_start, byte_end)` spans. Split out from `build_facets` so
+/// the offset math can be unit tested without depending on the
+/// `atrium_api` facet types.
+fn find_facet_spans(message: &str) -> Vec<(usize, usize)> {
+    let bytes = message.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'#' || b == b'$' {
+            let start = i;
+            let mut end = i + 1;
+            while end < bytes.len() && is_tag_byte(bytes[end]) {
+                end += 1;
+            }
+            if end > start + 1 {
+                spans.push((start, end));
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    spans
+}
+
+fn tag_facet(tag: String, byte_start: usize, byte_end: usize) -> facet::MainData {
+    facet::MainData {
+        features: vec![Union::Refs(facet::MainFeaturesItem::Tag(Box::new(
+            facet::TagData { tag }.into(),
+        )))],
+        index: facet::ByteSliceData {
+            byte_start,
+            byte_end,
+        }
+        .into(),
+    }
+}
+
+fn is_tag_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b == b','
+}
+
+/// Renders the day's intraday values as a small sparkline PNG and returns
+/// the encoded image bytes.
+pub fn render_sparkline(values: &[f64]) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    {
+        let root =
+            BitMapBackend::with_buffer(&mut buffer, (300, 100)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        let mut chart = ChartBuilder::on(&root)
+            .margin(5)
+            .build_cartesian_2d(0..values.len().max(1), min..max.max(min + f64::EPSILON))?;
+
+        chart.draw_series(LineSeries::new(
+            values.iter().enumerate().map(|(i, v)| (i, *v)),
+            &BLUE,
+        ))?;
+        root.present()?;
+    }
+    Ok(buffer)
+}
+
+/// Uploads the sparkline PNG as a blob and returns the
+/// `app.bsky.embed.images` embed for it, with `alt` describing the move.
+pub async fn build_sparkline_embed(
+    client: &BskyAgent,
+    values: &[f64],
+    alt: String,
+) -> Result<Union<images::MainData>> {
+    let png = render_sparkline(values)?;
+    let output = client.api.com.atproto.repo.upload_blob(png).await?;
+
+    let image = images::ImageData {
+        alt,
+        aspect_ratio: None,
+        image: output.data.blob,
+    };
+
+    Ok(Union::Refs(images::MainData {
+        images: vec![image.into()],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_offsets_after_multibyte_chars() {
+        // "às" has a 2-byte 'à' (0xC3 0xA0), so the byte offset of the
+        // tag must be computed from UTF-8 byte length, not char count.
+        let message = "subiu às 14:00 #IBOV";
+        let spans = find_facet_spans(message);
+
+        assert_eq!(spans, vec![(16, 21)]);
+        assert_eq!(&message.as_bytes()[16..21], "#IBOV".as_bytes());
+        assert_eq!(&message[16..21], "#IBOV");
+    }
+
+    #[test]
+    fn test_facets_cover_cashtag_and_hashtag_but_not_percentage() {
+        let message = "$BVSP subiu :) #IBOV - 134.567,89 (+2.34%) às 10:00 AM";
+        let spans = find_facet_spans(message);
+        let tags: Vec<&str> = spans.iter().map(|(s, e)| &message[*s..*e]).collect();
+
+        assert_eq!(tags, vec!["$BVSP", "#IBOV"]);
+    }
+
+    #[test]
+    fn test_build_facets_tag_text_excludes_marker() {
+        let facets = build_facets("#IBOV");
+        assert_eq!(facets.len(), 1);
+        match &facets[0].features[0] {
+            Union::Refs(facet::MainFeaturesItem::Tag(tag)) => assert_eq!(tag.tag, "IBOV"),
+            _ => panic!("expected a tag facet"),
+        }
+    }
+}
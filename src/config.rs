@@ -0,0 +1,90 @@
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use apalis_cron::Schedule;
+
+use crate::DEDUP_WINDOW;
+use crate::DEFAULT_CRONJOB;
+use crate::DEFAULT_DATABASE_URL;
+use crate::DEFAULT_METRICS_ADDR;
+
+/// Twitter API credentials, validated up front so a missing env var fails
+/// with a readable error instead of a panic deep inside `main`.
+#[derive(Clone)]
+pub struct TwitterConfig {
+    pub consumer_key: String,
+    pub consumer_secret: String,
+    pub access_token: String,
+    pub access_secret: String,
+}
+
+/// Bluesky login credentials.
+#[derive(Clone)]
+pub struct BskyConfig {
+    pub login: String,
+    pub password: String,
+}
+
+/// All configuration the worker needs, loaded and validated once at
+/// startup instead of via scattered `env::var(..).expect(..)` calls.
+pub struct Config {
+    pub schedule: Schedule,
+    pub database_url: String,
+    pub metrics_addr: SocketAddr,
+    pub dedup_window: Duration,
+    pub twitter: TwitterConfig,
+    pub bsky: BskyConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self> {
+        let cronjob = env::var("CRONJOB").unwrap_or_else(|_| DEFAULT_CRONJOB.to_string());
+        let schedule = Schedule::from_str(&cronjob)
+            .with_context(|| format!("Invalid CRONJOB expression: {cronjob}"))?;
+
+        let database_url =
+            env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string());
+
+        let metrics_addr = env::var("METRICS_ADDR")
+            .unwrap_or_else(|_| DEFAULT_METRICS_ADDR.to_string())
+            .parse::<SocketAddr>()
+            .context("Invalid METRICS_ADDR, expected host:port")?;
+
+        let dedup_window = match env::var("DEDUP_WINDOW_SECS") {
+            Ok(secs) => Duration::from_secs(
+                secs.parse()
+                    .context("Invalid DEDUP_WINDOW_SECS, expected an integer")?,
+            ),
+            Err(_) => DEDUP_WINDOW,
+        };
+
+        let twitter = TwitterConfig {
+            consumer_key: required_env("TWITTER_CONSUMER_KEY")?,
+            consumer_secret: required_env("TWITTER_CONSUMER_SECRET")?,
+            access_token: required_env("TWITTER_ACCESS_TOKEN")?,
+            access_secret: required_env("TWITTER_ACCESS_SECRET")?,
+        };
+
+        let bsky = BskyConfig {
+            login: required_env("BSKY_LOGIN")?,
+            password: required_env("BSKY_PASSWORD")?,
+        };
+
+        Ok(Self {
+            schedule,
+            database_url,
+            metrics_addr,
+            dedup_window,
+            twitter,
+            bsky,
+        })
+    }
+}
+
+fn required_env(key: &str) -> Result<String> {
+    env::var(key).with_context(|| format!("{key} must be set"))
+}
@@ -1,21 +1,36 @@
-use std::env;
-use std::str::FromStr;
+mod bsky_post;
+mod config;
+mod market_data;
+mod metrics;
+mod persistence;
+mod session;
+
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use adaptive_backoff::prelude::Backoff;
 use adaptive_backoff::prelude::BackoffBuilder;
 use adaptive_backoff::prelude::ExponentialBackoff;
 use adaptive_backoff::prelude::ExponentialBackoffBuilder;
+use anyhow::Context;
 use anyhow::Result;
 use apalis::prelude::*;
 use apalis_cron::CronStream;
-use apalis_cron::Schedule;
 use atrium_api::app::bsky::feed::post::RecordData;
 use bsky_sdk::BskyAgent;
 use chrono::Local;
 use chrono::{DateTime, Utc};
+use config::BskyConfig;
+use config::Config;
+use config::TwitterConfig;
 use dotenv::dotenv;
+use futures_util::StreamExt;
+use market_data::MarketDataSource;
+use market_data::WebSocketSource;
+use market_data::YahooPoller;
+use persistence::Platform;
+use persistence::Store;
 use separator::Separatable;
 use tokio::sync::Mutex;
 use tower::load_shed::LoadShedLayer;
@@ -24,73 +39,160 @@ use tracing::info;
 use tweety_rs::TweetyClient;
 
 const DEFAULT_CRONJOB: &str = "0 0,30 13-21 * * Mon-Fri";
-const BOVESPA_FETCH_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart/%5EBVSP?interval=1m&includePrePost=true&events=div%7Csplit%7Cearn&&lang=en-US&region=US";
+const DEFAULT_DATABASE_URL: &str = "sqlite://smsb.db";
+const BOVESPA_STREAM_URL: &str = "wss://streamer.finance.yahoo.com/";
+const BOVESPA_SYMBOL: &str = "^BVSP";
+const DEDUP_WINDOW: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_METRICS_ADDR: &str = "0.0.0.0:9000";
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const RETRY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<()> {
     dotenv().ok();
     tracing_subscriber::fmt::init();
 
-    let cronjob = env::var("CRONJOB").unwrap_or(DEFAULT_CRONJOB.to_string());
-    let twitter_client = create_twitter_client();
-    let bsky_client = create_bsky_client()
+    let config = Config::from_env().context("Invalid configuration")?;
+
+    let twitter_client = create_twitter_client(&config.twitter);
+    let bsky_client = create_bsky_client(&config.bsky)
         .await
-        .expect("Failed to create Bsky client");
-    let schedule = Schedule::from_str(&cronjob).unwrap();
-    let bovespa_value = Arc::new(Mutex::new(None::<f64>));
+        .context("Failed to create Bsky client")?;
     let backoff = create_backoff();
 
-    info!("Starting SMSB worker with cronjob: {}", cronjob);
+    let store = Store::connect(&config.database_url)
+        .await
+        .context("Failed to connect to the observation store")?;
+    let seeded_value = store
+        .last_value()
+        .await
+        .context("Failed to read last observed value")?;
+    let bovespa_value = Arc::new(Mutex::new(seeded_value));
+
+    metrics::install(config.metrics_addr).context("Failed to install Prometheus recorder")?;
+
+    info!("Starting SMSB worker");
+
+    let twitter_client = Arc::new(twitter_client);
+    let bsky_client = Arc::new(bsky_client);
+
+    let service = BovespaService {
+        bovespa_value,
+        intraday_values: Arc::new(Mutex::new((Local::now().date_naive(), Vec::new()))),
+        twitter_client: twitter_client.clone(),
+        backoff: Arc::new(Mutex::new(backoff)),
+        bsky_client: bsky_client.clone(),
+        source: Arc::new(YahooPoller),
+        store: Arc::new(store),
+        dedup_window: config.dedup_window,
+        bsky_config: Arc::new(config.bsky.clone()),
+    };
+
+    tokio::spawn(session::run_bsky_watchdog(
+        bsky_client.clone(),
+        config.bsky.clone(),
+    ));
+
+    let stream_source = WebSocketSource::new(BOVESPA_STREAM_URL, BOVESPA_SYMBOL);
+    tokio::spawn(run_stream_worker(service.clone(), stream_source));
+    tokio::spawn(run_retry_worker(service.clone()));
 
     let worker = WorkerBuilder::new("smsb")
         .enable_tracing()
         .layer(LoadShedLayer::new())
-        .data(BovespaService {
-            bovespa_value,
-            twitter_client: Arc::new(twitter_client),
-            backoff: Arc::new(Mutex::new(backoff)),
-            bsky_client: Arc::new(bsky_client),
-        })
-        .backend(CronStream::new(schedule))
+        .data(service)
+        .backend(CronStream::new(config.schedule))
         .build_fn(execute_bovespa);
-    Monitor::new()
-        .register(worker)
-        .run()
-        .await
-        .expect("Failed to run monitor");
+
+    let monitor = Monitor::new().register(worker).run();
+    tokio::select! {
+        result = monitor => result.context("Monitor exited with an error")?,
+        _ = shutdown_signal() => {
+            // `monitor` is dropped here, which cancels it outright rather
+            // than waiting for any in-flight job to finish — apalis gives
+            // us no graceful-drain handle to await instead, so don't claim
+            // one in the log.
+            info!("Shutdown signal received, exiting");
+        }
+    }
+
+    Ok(())
 }
 
-async fn create_bsky_client() -> Result<BskyAgent> {
-    let login = env::var("BSKY_LOGIN").expect("BSKY_LOGIN must be set");
-    let password = env::var("BSKY_PASSWORD").expect("BSKY_PASSWORD must be set");
+/// Resolves once SIGINT or (on Unix) SIGTERM is received, so `main` can
+/// race it against the apalis monitor and exit with a log line and a zero
+/// exit code. A job still running at that moment is cancelled, not drained.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install SIGINT handler");
+    };
 
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Feeds live ticks from `source` into the service alongside the cron
+/// schedule, only announcing a move once the price actually crosses the
+/// last-posted value (the per-tick debounce also protects against the
+/// socket replaying the same price).
+async fn run_stream_worker(svc: BovespaService, source: WebSocketSource) {
+    let Some(mut ticks) = source.stream() else {
+        return;
+    };
+    while let Some(tick) = ticks.next().await {
+        match tick {
+            Ok(value) => {
+                if let Err(e) = svc.handle_tick(value).await {
+                    error!("Failed to handle live tick: {}", e);
+                }
+            }
+            Err(e) => error!("Market data stream error: {}", e),
+        }
+    }
+}
+
+async fn create_bsky_client(config: &BskyConfig) -> Result<BskyAgent> {
     let agent = BskyAgent::builder().build().await?;
-    agent.login(login, password).await?;
+    agent.login(&config.login, &config.password).await?;
 
     Ok(agent)
 }
 
-fn create_twitter_client() -> TweetyClient {
-    let consumer_key = env::var("TWITTER_CONSUMER_KEY").expect("TWITTER_CONSUMER_KEY must be set");
-    let consumer_secret =
-        env::var("TWITTER_CONSUMER_SECRET").expect("TWITTER_CONSUMER_SECRET must be set");
-    let access_token = env::var("TWITTER_ACCESS_TOKEN").expect("TWITTER_ACCESS_TOKEN must be set");
-    let access_secret =
-        env::var("TWITTER_ACCESS_SECRET").expect("TWITTER_ACCESS_SECRET must be set");
-
+fn create_twitter_client(config: &TwitterConfig) -> TweetyClient {
     TweetyClient::new(
-        &consumer_key,
-        &access_token,
-        &consumer_secret,
-        &access_secret,
+        &config.consumer_key,
+        &config.access_token,
+        &config.consumer_secret,
+        &config.access_secret,
     )
 }
 
 #[derive(Clone)]
 struct BovespaService {
     bovespa_value: Arc<Mutex<Option<f64>>>,
+    intraday_values: Arc<Mutex<(chrono::NaiveDate, Vec<f64>)>>,
     twitter_client: Arc<TweetyClient>,
     backoff: Arc<Mutex<ExponentialBackoff>>,
     bsky_client: Arc<BskyAgent>,
+    source: Arc<dyn MarketDataSource>,
+    store: Arc<Store>,
+    dedup_window: Duration,
+    bsky_config: Arc<BskyConfig>,
 }
 impl BovespaService {
     async fn execute(&self, job: Job) -> Result<()> {
@@ -103,7 +205,9 @@ impl BovespaService {
                 }
                 Err(e) => {
                     error!("Failed to execute job: {}", e);
-                    tokio::time::sleep(backoff.wait()).await;
+                    let delay = backoff.wait();
+                    metrics::set_backoff_delay(delay);
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
@@ -111,16 +215,36 @@ impl BovespaService {
     }
 
     async fn execute_inner(&self, job: Job) -> Result<()> {
-        dbg!(&job.0);
-        let new_value = fetch_bovespa().await?;
-        let mut guard = self.bovespa_value.lock().await;
-        let last_value = guard.unwrap_or(new_value);
-        *guard = Some(new_value);
-        drop(guard);
+        info!("Running cron job scheduled for {}", job.0);
+        let started = Instant::now();
+        let result = self.source.latest().await;
+        metrics::record_fetch(result.is_ok(), started.elapsed());
+        self.handle_tick(result?).await
+    }
+
+    /// Compares `new_value` against the last-*posted* value and announces a
+    /// move if it crosses it. Shared by the cron-driven poll and the live
+    /// WebSocket stream so both only post on an actual price change.
+    ///
+    /// `bovespa_value` only advances once we commit to announcing a move
+    /// (past the dedup check below), never on every observed tick — a live
+    /// WebSocket feed ticks on every micro-fluctuation, and comparing
+    /// against the last *seen* value instead of the last *posted* one would
+    /// flood both platforms with "subiu"/"caiu" for noise.
+    async fn handle_tick(&self, new_value: f64) -> Result<()> {
+        metrics::set_last_value(new_value);
+        let last_value = self.bovespa_value.lock().await.unwrap_or(new_value);
+
+        let intraday_snapshot = self.push_intraday_value(new_value).await;
 
         let formatted_value = separate_decimals_brazilian(new_value);
+        let pct_change = if last_value.abs() > f64::EPSILON {
+            (new_value - last_value) / last_value * 100.0
+        } else {
+            0.0
+        };
 
-        let msg = if (new_value - last_value).abs() < f64::EPSILON {
+        let (direction, msg) = if (new_value - last_value).abs() < f64::EPSILON {
             info!(
                 "A Bovespa não mudou :| - {} às {}",
                 formatted_value,
@@ -128,58 +252,178 @@ impl BovespaService {
             );
             return Ok(());
         } else if new_value > last_value {
-            format!(
-                "A Bovespa subiu :) - {} às {}",
-                formatted_value,
-                Local::now().format("%I:%M %p")
+            (
+                "up",
+                format!(
+                    "$BVSP subiu :) #IBOV - {} (+{:.2}%) às {}",
+                    formatted_value,
+                    pct_change,
+                    Local::now().format("%I:%M %p")
+                ),
             )
         } else {
-            format!(
-                "A Bovespa caiu :( - {} às {}",
-                formatted_value,
-                Local::now().format("%I:%M %p")
+            (
+                "down",
+                format!(
+                    "$BVSP caiu :( #IBOV - {} ({:.2}%) às {}",
+                    formatted_value,
+                    pct_change,
+                    Local::now().format("%I:%M %p")
+                ),
             )
         };
+
+        if self
+            .store
+            .already_posted_recently(direction, new_value, self.dedup_window)
+            .await?
+        {
+            info!("Skipping duplicate post for {} {}", direction, formatted_value);
+            return Ok(());
+        }
+
         info!("{}", msg);
-        post_tweet(self.twitter_client.clone(), &msg).await;
-        post_bsky(self.bsky_client.clone(), &msg).await?;
+        let observation_id = self.store.record(new_value, direction, &msg).await?;
+        *self.bovespa_value.lock().await = Some(new_value);
+
+        let started = Instant::now();
+        let tweet_ok = post_tweet(self.twitter_client.clone(), &msg).await;
+        metrics::record_post(metrics::Platform::Twitter, tweet_ok, started.elapsed());
+        self.store
+            .mark_posted(observation_id, Platform::Twitter, tweet_ok)
+            .await?;
+
+        let started = Instant::now();
+        let bsky_ok = post_bsky(
+            self.bsky_client.clone(),
+            &self.bsky_config,
+            &msg,
+            &intraday_snapshot,
+        )
+        .await;
+        metrics::record_post(metrics::Platform::Bsky, bsky_ok.is_ok(), started.elapsed());
+        self.store
+            .mark_posted(observation_id, Platform::Bsky, bsky_ok.is_ok())
+            .await?;
+        bsky_ok?;
+
         Ok(())
     }
-}
 
-async fn fetch_bovespa() -> Result<f64> {
-    info!("Fetching Bovespa value from {}", BOVESPA_FETCH_URL);
-    let client = reqwest::ClientBuilder::new()
-        .user_agent("Mozilla/5.0 (X11; Linux x86_64)")
-        .build()?;
-
-    let response = client
-        .get(BOVESPA_FETCH_URL)
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
+    /// Appends to today's intraday values and returns a snapshot for the
+    /// sparkline, resetting the buffer whenever the local date rolls over
+    /// so the chart stays "today's" values instead of growing forever.
+    async fn push_intraday_value(&self, value: f64) -> Vec<f64> {
+        let today = Local::now().date_naive();
+        let mut guard = self.intraday_values.lock().await;
+        if guard.0 != today {
+            *guard = (today, Vec::new());
+        }
+        guard.1.push(value);
+        guard.1.clone()
+    }
+
+    /// Re-attempts posting to any platform that previously failed, using
+    /// the message text stored alongside the observation. A Twitter
+    /// failure doesn't block retrying Bluesky and vice versa, since each
+    /// platform is retried independently off its own `pending_retries`.
+    async fn retry_pending(&self) -> Result<()> {
+        for observation in self.store.pending_retries(Platform::Twitter, RETRY_WINDOW).await? {
+            info!("Retrying Twitter post for observation {}", observation.id);
+            let ok = post_tweet(self.twitter_client.clone(), &observation.message).await;
+            self.store
+                .mark_posted(observation.id, Platform::Twitter, ok)
+                .await?;
+        }
+
+        let intraday_snapshot = self.intraday_values.lock().await.1.clone();
+        for observation in self.store.pending_retries(Platform::Bsky, RETRY_WINDOW).await? {
+            info!("Retrying Bsky post for observation {}", observation.id);
+            let ok = post_bsky(
+                self.bsky_client.clone(),
+                &self.bsky_config,
+                &observation.message,
+                &intraday_snapshot,
+            )
+            .await
+            .is_ok();
+            self.store
+                .mark_posted(observation.id, Platform::Bsky, ok)
+                .await?;
+        }
 
-    let value = response["chart"]["result"][0]["meta"]["regularMarketPrice"]
-        .as_f64()
-        .ok_or_else(|| anyhow::anyhow!("Failed to parse value"))?;
-    Ok(value)
+        Ok(())
+    }
 }
 
-async fn post_tweet(client: Arc<TweetyClient>, message: &str) {
+/// Periodically re-posts recent observations that previously failed on a
+/// platform, each platform on its own, whether or not the other one
+/// succeeded.
+async fn run_retry_worker(svc: BovespaService) {
+    loop {
+        tokio::time::sleep(RETRY_INTERVAL).await;
+        if let Err(e) = svc.retry_pending().await {
+            error!("Failed to retry pending posts: {}", e);
+        }
+    }
+}
+
+async fn post_tweet(client: Arc<TweetyClient>, message: &str) -> bool {
     match client.post_tweet(message, None).await {
-        Ok(_) => info!("Tweet posted successfully: {}", message),
-        Err(e) => error!("Failed to post tweet: {}", e),
+        Ok(_) => {
+            info!("Tweet posted successfully: {}", message);
+            true
+        }
+        Err(e) => {
+            error!("Failed to post tweet: {}", e);
+            false
+        }
     }
 }
 
-async fn post_bsky(client: Arc<BskyAgent>, message: &str) -> Result<()> {
+async fn post_bsky(
+    client: Arc<BskyAgent>,
+    config: &BskyConfig,
+    message: &str,
+    intraday_values: &[f64],
+) -> Result<()> {
+    match create_bsky_record(&client, message, intraday_values).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            if session::refresh_on_auth_error(&client, config, &e).await {
+                return create_bsky_record(&client, message, intraday_values).await;
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn create_bsky_record(
+    client: &BskyAgent,
+    message: &str,
+    intraday_values: &[f64],
+) -> Result<()> {
+    let facets = bsky_post::build_facets(message)
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    let embed = match bsky_post::build_sparkline_embed(client, intraday_values, message.to_string())
+        .await
+    {
+        Ok(embed) => Some(embed),
+        Err(e) => {
+            error!("Failed to build sparkline embed: {}", e);
+            None
+        }
+    };
+
     client
         .create_record(RecordData {
             created_at: atrium_api::types::string::Datetime::now(),
-            embed: None,
+            embed,
             entities: None,
-            facets: None,
+            facets: Some(facets),
             labels: None,
             langs: None,
             reply: None,
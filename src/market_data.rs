@@ -0,0 +1,271 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use adaptive_backoff::prelude::Backoff;
+use adaptive_backoff::prelude::BackoffBuilder;
+use adaptive_backoff::prelude::ExponentialBackoff;
+use adaptive_backoff::prelude::ExponentialBackoffBuilder;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Mutex;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+const BOVESPA_FETCH_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart/%5EBVSP?interval=1m&includePrePost=true&events=div%7Csplit%7Cearn&&lang=en-US&region=US";
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A source of Bovespa index prices. Implementations may poll on demand
+/// via [`MarketDataSource::latest`], push continuous updates via
+/// [`MarketDataSource::stream`], or both.
+#[async_trait]
+pub trait MarketDataSource: Send + Sync {
+    /// Fetch a single up-to-date price.
+    async fn latest(&self) -> Result<f64>;
+
+    /// Open a continuous stream of price updates. Sources that are
+    /// poll-only (e.g. the Yahoo Finance HTTP poller) return `None`.
+    fn stream(&self) -> Option<std::pin::Pin<Box<dyn Stream<Item = Result<f64>> + Send>>> {
+        None
+    }
+}
+
+/// Polls the Yahoo Finance chart endpoint for the current Bovespa price.
+/// This is the original fetch mechanism, kept as the cron-driven fallback.
+#[derive(Default)]
+pub struct YahooPoller;
+
+#[async_trait]
+impl MarketDataSource for YahooPoller {
+    async fn latest(&self) -> Result<f64> {
+        info!("Fetching Bovespa value from {}", BOVESPA_FETCH_URL);
+        let client = reqwest::ClientBuilder::new()
+            .user_agent("Mozilla/5.0 (X11; Linux x86_64)")
+            .build()?;
+
+        let response = client
+            .get(BOVESPA_FETCH_URL)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+
+        let value = response["chart"]["result"][0]["meta"]["regularMarketPrice"]
+            .as_f64()
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse value"))?;
+        Ok(value)
+    }
+}
+
+/// Streams Bovespa ticks over a persistent WebSocket connection,
+/// reconnecting with an [`ExponentialBackoff`] whenever the socket drops.
+pub struct WebSocketSource {
+    url: String,
+    symbol: String,
+    backoff: Arc<Mutex<ExponentialBackoff>>,
+}
+
+impl WebSocketSource {
+    pub fn new(url: impl Into<String>, symbol: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            symbol: symbol.into(),
+            backoff: Arc::new(Mutex::new(default_backoff())),
+        }
+    }
+}
+
+fn default_backoff() -> ExponentialBackoff {
+    ExponentialBackoffBuilder::default()
+        .factor(1.1)
+        .min(Duration::from_secs(1))
+        .max(Duration::from_secs(300))
+        .build()
+        .unwrap()
+}
+
+#[async_trait]
+impl MarketDataSource for WebSocketSource {
+    async fn latest(&self) -> Result<f64> {
+        // The WebSocket source has no request/response round-trip; callers
+        // that need a single value should use `YahooPoller` instead.
+        Err(anyhow::anyhow!(
+            "WebSocketSource does not support one-shot polling, use stream()"
+        ))
+    }
+
+    fn stream(&self) -> Option<std::pin::Pin<Box<dyn Stream<Item = Result<f64>> + Send>>> {
+        let url = self.url.clone();
+        let symbol = self.symbol.clone();
+        let backoff = self.backoff.clone();
+
+        let stream = async_stream::try_stream! {
+            loop {
+                match connect_and_subscribe(&url, &symbol).await {
+                    Ok(mut socket) => {
+                        backoff.lock().await.reset();
+                        loop {
+                            tokio::select! {
+                                msg = socket.next() => {
+                                    match msg {
+                                        Some(Ok(Message::Text(text))) => {
+                                            if let Some(price) = parse_tick(&text) {
+                                                yield price;
+                                            }
+                                        }
+                                        Some(Ok(Message::Ping(payload))) => {
+                                            let _ = socket.send(Message::Pong(payload)).await;
+                                        }
+                                        Some(Ok(Message::Close(_))) | None => {
+                                            warn!("Market data socket closed, reconnecting");
+                                            break;
+                                        }
+                                        Some(Err(e)) => {
+                                            error!("Market data socket error: {}", e);
+                                            break;
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                                _ = tokio::time::sleep(PING_INTERVAL) => {
+                                    if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                                        warn!("Failed to send ping, reconnecting");
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to connect market data socket: {}", e);
+                    }
+                }
+
+                let wait = backoff.lock().await.wait();
+                tokio::time::sleep(wait).await;
+            }
+        };
+
+        Some(Box::pin(stream))
+    }
+}
+
+async fn connect_and_subscribe(
+    url: &str,
+    symbol: &str,
+) -> Result<tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>
+{
+    let (mut socket, _) = connect_async(url).await?;
+    let subscribe = serde_json::json!({ "subscribe": [symbol] }).to_string();
+    socket.send(Message::Text(subscribe)).await?;
+    Ok(socket)
+}
+
+/// The Yahoo Finance streamer wraps each tick in a JSON envelope whose
+/// `message` field is a base64-encoded `PricingData` protobuf (see e.g.
+/// the widely mirrored `yahoo_finance_streamer.proto` schema), not plain
+/// JSON with a `price` field. We only care about the price, so rather
+/// than pull in a full prost-generated schema for one field, decode the
+/// envelope and scan the protobuf wire format by hand for field 2
+/// (`price`, a fixed32 `float`).
+fn parse_tick(text: &str) -> Option<f64> {
+    let envelope: serde_json::Value = serde_json::from_str(text).ok()?;
+    let encoded = envelope["message"].as_str()?;
+    let bytes = BASE64.decode(encoded).ok()?;
+    decode_price_field(&bytes)
+}
+
+/// Scans a `PricingData` protobuf message for field number 2 (`price`,
+/// wire type 5 / fixed32) and returns it as `f64`. Other fields are
+/// skipped using their wire type so the scan stays in sync regardless of
+/// field order.
+fn decode_price_field(bytes: &[u8]) -> Option<f64> {
+    const PRICE_FIELD: u64 = 2;
+    const WIRE_TYPE_VARINT: u64 = 0;
+    const WIRE_TYPE_FIXED64: u64 = 1;
+    const WIRE_TYPE_LEN: u64 = 2;
+    const WIRE_TYPE_FIXED32: u64 = 5;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let (tag, consumed) = read_varint(&bytes[i..])?;
+        i += consumed;
+        let field_number = tag >> 3;
+        let wire_type = tag & 0x7;
+
+        match wire_type {
+            WIRE_TYPE_VARINT => {
+                let (_, consumed) = read_varint(&bytes[i..])?;
+                i += consumed;
+            }
+            WIRE_TYPE_FIXED64 => i += 8,
+            WIRE_TYPE_LEN => {
+                let (len, consumed) = read_varint(&bytes[i..])?;
+                i += consumed + len as usize;
+            }
+            WIRE_TYPE_FIXED32 => {
+                let word: [u8; 4] = bytes.get(i..i + 4)?.try_into().ok()?;
+                if field_number == PRICE_FIELD {
+                    return Some(f32::from_le_bytes(word) as f64);
+                }
+                i += 4;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Reads a protobuf base-128 varint, returning the value and the number of
+/// bytes consumed.
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a minimal `PricingData` protobuf with only field 2 (price)
+    /// set, matching what a real tick frame carries for our purposes.
+    fn encode_pricing_data(price: f32) -> Vec<u8> {
+        let tag = (2u8 << 3) | 5; // field 2, wire type 5 (fixed32)
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&price.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_parse_tick_decodes_base64_protobuf_envelope() {
+        let payload = encode_pricing_data(134_567.89);
+        let encoded = BASE64.encode(payload);
+        let frame = serde_json::json!({ "type": "pricing", "message": encoded }).to_string();
+
+        let price = parse_tick(&frame).expect("expected a parsed price");
+        assert!((price - 134_567.89_f64).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_tick_ignores_fields_before_price() {
+        let mut payload = vec![(1u8 << 3) | 2, 4]; // field 1 (string, len-delimited), length 4
+        payload.extend_from_slice(b"BVSP");
+        payload.extend(encode_pricing_data(99.5));
+        let encoded = BASE64.encode(payload);
+        let frame = serde_json::json!({ "type": "pricing", "message": encoded }).to_string();
+
+        let price = parse_tick(&frame).expect("expected a parsed price");
+        assert!((price - 99.5_f64).abs() < 0.01);
+    }
+}
@@ -0,0 +1,61 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Result;
+use metrics::counter;
+use metrics::gauge;
+use metrics::histogram;
+use metrics_exporter_prometheus::PrometheusBuilder;
+
+/// Platform a post was attempted on, used to label the post counters and
+/// histograms.
+#[derive(Debug, Clone, Copy)]
+pub enum Platform {
+    Twitter,
+    Bsky,
+}
+
+impl Platform {
+    fn label(self) -> &'static str {
+        match self {
+            Platform::Twitter => "twitter",
+            Platform::Bsky => "bsky",
+        }
+    }
+}
+
+/// Installs the Prometheus recorder and serves `/metrics` on `addr`. Must
+/// be called once before any of the `record_*`/`set_*` helpers below.
+pub fn install(addr: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()?;
+    Ok(())
+}
+
+pub fn record_fetch(success: bool, latency: Duration) {
+    if success {
+        counter!("smsb_fetch_success_total").increment(1);
+    } else {
+        counter!("smsb_fetch_failure_total").increment(1);
+    }
+    histogram!("smsb_fetch_latency_seconds").record(latency.as_secs_f64());
+}
+
+pub fn record_post(platform: Platform, success: bool, latency: Duration) {
+    let label = platform.label();
+    if success {
+        counter!("smsb_post_success_total", "platform" => label).increment(1);
+    } else {
+        counter!("smsb_post_failure_total", "platform" => label).increment(1);
+    }
+    histogram!("smsb_post_latency_seconds", "platform" => label).record(latency.as_secs_f64());
+}
+
+pub fn set_last_value(value: f64) {
+    gauge!("smsb_bovespa_last_value").set(value);
+}
+
+pub fn set_backoff_delay(delay: Duration) {
+    gauge!("smsb_backoff_delay_seconds").set(delay.as_secs_f64());
+}
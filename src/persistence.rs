@@ -0,0 +1,165 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::Row;
+use sqlx::SqlitePool;
+
+/// Which platform a post was attempted on, used to key the per-platform
+/// success columns on `observations`.
+#[derive(Debug, Clone, Copy)]
+pub enum Platform {
+    Twitter,
+    Bsky,
+}
+
+impl Platform {
+    fn column(self) -> &'static str {
+        match self {
+            Platform::Twitter => "twitter_posted",
+            Platform::Bsky => "bsky_posted",
+        }
+    }
+}
+
+/// One observed Bovespa value and whether it was successfully posted to
+/// each platform. Persisted so the worker can survive restarts without
+/// re-announcing a stale direction and can retry a platform that failed
+/// independently of the other.
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub id: i64,
+    pub observed_at: DateTime<Utc>,
+    pub value: f64,
+    pub direction: String,
+    pub message: String,
+    pub twitter_posted: bool,
+    pub bsky_posted: bool,
+}
+
+/// SQLite-backed store for observations, used to seed `last_value` on
+/// startup and to dedup/retry posts across crashes and deploys.
+pub struct Store {
+    pool: SqlitePool,
+}
+
+impl Store {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        // sqlx's default `SqliteConnectOptions` has `create_if_missing(false)`,
+        // so on a fresh deploy with no database file yet this would fail
+        // with "unable to open database file" before the store ever exists.
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        let store = Self { pool };
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    async fn migrate(&self) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS observations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                observed_at TEXT NOT NULL,
+                value REAL NOT NULL,
+                direction TEXT NOT NULL,
+                message TEXT NOT NULL,
+                twitter_posted INTEGER NOT NULL DEFAULT 0,
+                bsky_posted INTEGER NOT NULL DEFAULT 0
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Seeds `last_value` from the most recently persisted observation,
+    /// instead of defaulting to whatever value is first fetched.
+    pub async fn last_value(&self) -> Result<Option<f64>> {
+        let row = sqlx::query("SELECT value FROM observations ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| r.get::<f64, _>("value")))
+    }
+
+    /// Returns `true` if the same direction/value was already posted
+    /// within `window`, so the caller can skip announcing it again.
+    pub async fn already_posted_recently(
+        &self,
+        direction: &str,
+        value: f64,
+        window: Duration,
+    ) -> Result<bool> {
+        let since = Utc::now() - chrono::Duration::from_std(window)?;
+        let row = sqlx::query(
+            "SELECT COUNT(*) as count FROM observations
+             WHERE direction = ? AND value = ? AND observed_at >= ?",
+        )
+        .bind(direction)
+        .bind(value)
+        .bind(since.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get::<i64, _>("count") > 0)
+    }
+
+    pub async fn record(&self, value: f64, direction: &str, message: &str) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO observations (observed_at, value, direction, message) VALUES (?, ?, ?, ?)",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(value)
+        .bind(direction)
+        .bind(message)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn mark_posted(&self, id: i64, platform: Platform, success: bool) -> Result<()> {
+        let query = format!(
+            "UPDATE observations SET {} = ? WHERE id = ?",
+            platform.column()
+        );
+        sqlx::query(&query)
+            .bind(success)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Observations that failed to post on `platform` but are recent
+    /// enough to still be worth retrying.
+    pub async fn pending_retries(&self, platform: Platform, since: Duration) -> Result<Vec<Observation>> {
+        let cutoff = Utc::now() - chrono::Duration::from_std(since)?;
+        let query = format!(
+            "SELECT id, observed_at, value, direction, message, twitter_posted, bsky_posted
+             FROM observations
+             WHERE {} = 0 AND observed_at >= ?
+             ORDER BY id ASC",
+            platform.column()
+        );
+        let rows = sqlx::query(&query)
+            .bind(cutoff.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Observation {
+                    id: row.get("id"),
+                    observed_at: DateTime::parse_from_rfc3339(row.get::<String, _>("observed_at").as_str())?
+                        .with_timezone(&Utc),
+                    value: row.get("value"),
+                    direction: row.get("direction"),
+                    message: row.get("message"),
+                    twitter_posted: row.get("twitter_posted"),
+                    bsky_posted: row.get("bsky_posted"),
+                })
+            })
+            .collect()
+    }
+}
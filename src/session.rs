@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use bsky_sdk::BskyAgent;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::config::BskyConfig;
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Returns true if `err`'s message looks like an expired or otherwise
+/// rejected AT Protocol session, as opposed to a transient network error.
+pub fn is_auth_error(err: &anyhow::Error) -> bool {
+    let message = err.to_string().to_lowercase();
+    message.contains("expiredtoken")
+        || message.contains("authenticationrequired")
+        || message.contains("unauthorized")
+        || message.contains("invalidtoken")
+}
+
+/// Re-logs in to Bluesky using the stored credentials. `BskyAgent` already
+/// refreshes access tokens from its stored refresh token on each request;
+/// this is the fallback for when that refresh itself has expired.
+async fn relogin(agent: &BskyAgent, config: &BskyConfig) -> Result<()> {
+    agent.login(&config.login, &config.password).await?;
+    info!("Re-authenticated Bluesky session");
+    Ok(())
+}
+
+/// If `agent`'s session looks like it has expired, re-logs in so the
+/// caller can retry the request that just failed.
+pub async fn refresh_on_auth_error(
+    agent: &BskyAgent,
+    config: &BskyConfig,
+    err: &anyhow::Error,
+) -> bool {
+    if !is_auth_error(err) {
+        return false;
+    }
+    warn!("Bluesky session looks expired, re-authenticating");
+    match relogin(agent, config).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Failed to re-authenticate Bluesky session: {}", e);
+            false
+        }
+    }
+}
+
+/// Periodically verifies the Bluesky session is still valid, refreshing
+/// it proactively instead of waiting for `post_bsky` to hit an auth error.
+/// AT Protocol access JWTs expire after a few hours, so a long-running
+/// process needs this even if it never posts during the idle window.
+pub async fn run_bsky_watchdog(agent: Arc<BskyAgent>, config: BskyConfig) {
+    loop {
+        tokio::time::sleep(WATCHDOG_INTERVAL).await;
+
+        if let Err(e) = agent.api.com.atproto.server.get_session().await {
+            warn!("Bluesky session check failed: {}", e);
+            if let Err(e) = relogin(&agent, &config).await {
+                error!("Failed to re-authenticate Bluesky session: {}", e);
+            }
+        }
+    }
+}